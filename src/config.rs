@@ -0,0 +1,431 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::env;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::Args;
+
+const ENV_PREFIX: &str = "NAT_FAILOVER_";
+
+const DEFAULT_TO: &str = "2001:4860:4860::8888";
+const DEFAULT_RETRIES: usize = 5;
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// One notifier entry under `notifiers` in the config file.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    Alertmanager {
+        url: String,
+    },
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_method")]
+        method: String,
+        #[serde(default)]
+        body_template: Option<String>,
+    },
+    Shell {
+        command: String,
+    },
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+/// The reachability check run for a watch target, in place of the hard-coded ICMP ping.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProbeConfig {
+    /// The original behavior: ping `to` (falls back to the watch's top-level `to` if unset).
+    Icmp {
+        #[serde(default)]
+        to: Option<IpAddr>,
+    },
+    TcpConnect {
+        host: String,
+        port: u16,
+    },
+    Http {
+        url: String,
+        #[serde(default = "default_min_status")]
+        min_status: u16,
+        #[serde(default = "default_max_status")]
+        max_status: u16,
+    },
+}
+
+fn default_min_status() -> u16 {
+    200
+}
+
+fn default_max_status() -> u16 {
+    399
+}
+
+/// Configures leader election between several routers watching the same prefix, backed by a
+/// Consul KV session lock, so only the elected leader injects the NAT masquerade rule.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ConsulConfig {
+    /// Base URL of the Consul HTTP API, e.g. `http://127.0.0.1:8500`.
+    pub url: String,
+
+    /// KV prefix under which the leader lock and cluster state are stored.
+    pub key_prefix: String,
+
+    /// Identifies this node in the cluster state. Defaults to the local hostname.
+    #[serde(default)]
+    pub node_id: Option<String>,
+
+    /// Address the `/health` endpoint listens on.
+    #[serde(default = "default_health_addr")]
+    pub health_addr: String,
+}
+
+fn default_health_addr() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+/// One `[[watch]]` entry from the config file, or the top-level defaults it falls back to.
+/// Every field is optional so a file only has to set what it wants to override.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct WatchOpts {
+    pub iface: Option<String>,
+    pub from: Option<IpAddr>,
+    pub to: Option<IpAddr>,
+    pub retries: Option<usize>,
+    pub timeout_ms: Option<u64>,
+    pub interval_secs: Option<u64>,
+    pub alertmanager_url: Option<String>,
+    pub notifiers: Vec<NotifierConfig>,
+    pub probe: Option<ProbeConfig>,
+
+    /// Several independent probe targets to guard against one of them flapping. Takes
+    /// precedence over `probe` when non-empty.
+    #[serde(rename = "target")]
+    pub targets: Vec<ProbeConfig>,
+
+    /// How many of `targets` must succeed for a path to be considered up. Defaults to half of
+    /// them, rounded up (`(targets.len() + 1) / 2`).
+    pub quorum: Option<usize>,
+
+    /// Number of consecutive opposing decisions required before flipping the NAT state, to
+    /// absorb a single noisy cycle. Defaults to 1 (react immediately).
+    pub hysteresis: Option<usize>,
+
+    /// Consul-backed leader election for highly-available router pairs. Unset means this
+    /// node always acts alone, as before.
+    pub consul: Option<ConsulConfig>,
+}
+
+impl WatchOpts {
+    /// Reads overrides from `NAT_FAILOVER_*` environment variables.
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            iface: env_var(ENV_PREFIX, "IFACE"),
+            from: env_parsed(ENV_PREFIX, "FROM")?,
+            to: env_parsed(ENV_PREFIX, "TO")?,
+            retries: env_parsed(ENV_PREFIX, "RETRIES")?,
+            timeout_ms: env_parsed(ENV_PREFIX, "TIMEOUT_MS")?,
+            interval_secs: env_parsed(ENV_PREFIX, "INTERVAL_SECS")?,
+            alertmanager_url: env_var(ENV_PREFIX, "ALERTMANAGER_URL"),
+            notifiers: Vec::new(),
+            probe: None,
+            targets: Vec::new(),
+            quorum: env_parsed(ENV_PREFIX, "QUORUM")?,
+            hysteresis: env_parsed(ENV_PREFIX, "HYSTERESIS")?,
+            consul: None,
+        })
+    }
+
+    /// Lifts the flags the user actually passed on the CLI. `Args` fields are `Option`s so
+    /// that a flag left unset doesn't shadow a value from the environment or the file.
+    fn from_args(args: &Args) -> Self {
+        Self {
+            iface: args.iface.clone(),
+            from: args.from,
+            to: args.to,
+            retries: args.retries,
+            timeout_ms: args.timeout.map(|d| d.as_millis() as u64),
+            interval_secs: args.interval.map(|d| d.as_secs()),
+            alertmanager_url: args.alertmanager_url.clone(),
+            notifiers: Vec::new(),
+            probe: None,
+            targets: Vec::new(),
+            quorum: None,
+            hysteresis: None,
+            consul: None,
+        }
+    }
+
+    /// Overlays `other` on top of `self`, with `other` winning wherever it sets a field.
+    fn overlay(&self, other: &WatchOpts) -> WatchOpts {
+        WatchOpts {
+            iface: other.iface.clone().or_else(|| self.iface.clone()),
+            from: other.from.or(self.from),
+            to: other.to.or(self.to),
+            retries: other.retries.or(self.retries),
+            timeout_ms: other.timeout_ms.or(self.timeout_ms),
+            interval_secs: other.interval_secs.or(self.interval_secs),
+            alertmanager_url: other
+                .alertmanager_url
+                .clone()
+                .or_else(|| self.alertmanager_url.clone()),
+            notifiers: if other.notifiers.is_empty() {
+                self.notifiers.clone()
+            } else {
+                other.notifiers.clone()
+            },
+            probe: other.probe.clone().or_else(|| self.probe.clone()),
+            targets: if other.targets.is_empty() {
+                self.targets.clone()
+            } else {
+                other.targets.clone()
+            },
+            quorum: other.quorum.or(self.quorum),
+            hysteresis: other.hysteresis.or(self.hysteresis),
+            consul: other.consul.clone().or_else(|| self.consul.clone()),
+        }
+    }
+}
+
+/// Raw shape of the `--config` file: defaults shared by every watch, plus zero or more
+/// `[[watch]]` entries. Deserialized as-is; `WatchConfig::try_from` does the validation.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ConfigOpts {
+    #[serde(flatten)]
+    pub defaults: WatchOpts,
+
+    #[serde(rename = "watch")]
+    pub watches: Vec<WatchOpts>,
+
+    /// Address the supervisor's aggregated `/health` endpoint listens on. Global, unlike the
+    /// per-watch `consul.health_addr`, since one supervisor process watches every target.
+    #[serde(default)]
+    pub health_addr: Option<String>,
+}
+
+impl ConfigOpts {
+    /// Loads and parses a config file, picking TOML or YAML based on the file extension.
+    fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&raw)
+                .with_context(|| format!("parsing YAML config file {}", path.display())),
+            _ => toml::from_str(&raw)
+                .with_context(|| format!("parsing TOML config file {}", path.display())),
+        }
+    }
+}
+
+/// A single, fully resolved watch target. Every field `DetectionLoop` needs is present --
+/// no more `Option`s past this point.
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    pub iface: String,
+    pub from: IpAddr,
+    pub to: IpAddr,
+    pub retries: usize,
+    pub timeout: Duration,
+    pub interval: Duration,
+    pub notifiers: Vec<NotifierConfig>,
+
+    /// One or more independent probe targets. A path is "up" when at least `quorum` of them
+    /// succeed, "down" when fewer than `quorum` of them succeed.
+    pub targets: Vec<ProbeConfig>,
+    pub quorum: usize,
+
+    /// Number of consecutive opposing decisions required before flipping the NAT state.
+    pub hysteresis: usize,
+
+    pub consul: Option<ConsulConfig>,
+}
+
+impl TryFrom<WatchOpts> for WatchConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(opts: WatchOpts) -> Result<Self> {
+        // The `alertmanager_url` flag predates the generic notifier list; keep it working by
+        // turning it into a single Alertmanager notifier when no `notifiers` are configured.
+        let notifiers = if !opts.notifiers.is_empty() {
+            opts.notifiers
+        } else if let Some(url) = opts.alertmanager_url {
+            vec![NotifierConfig::Alertmanager { url }]
+        } else {
+            Vec::new()
+        };
+
+        let to = opts.to.unwrap_or(IpAddr::from_str(DEFAULT_TO).unwrap());
+        let targets = if !opts.targets.is_empty() {
+            opts.targets
+        } else {
+            vec![opts.probe.unwrap_or(ProbeConfig::Icmp { to: Some(to) })]
+        };
+        // Defaults to half the targets rounded up, not unanimity: with several independent targets, requiring
+        // every single one to succeed would make one flaky target cause the exact false-positive
+        // failover this feature exists to avoid.
+        let quorum = std::cmp::min(opts.quorum.unwrap_or((targets.len() + 1) / 2), targets.len()).max(1);
+
+        Ok(Self {
+            iface: opts
+                .iface
+                .ok_or_else(|| anyhow!("missing `iface` (set --iface, NAT_FAILOVER_IFACE, or the config file)"))?,
+            from: opts
+                .from
+                .ok_or_else(|| anyhow!("missing `from` (set --from, NAT_FAILOVER_FROM, or the config file)"))?,
+            to,
+            retries: std::cmp::max(opts.retries.unwrap_or(DEFAULT_RETRIES), 1),
+            timeout: opts
+                .timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_TIMEOUT),
+            interval: opts
+                .interval_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_INTERVAL),
+            notifiers,
+            targets,
+            quorum,
+            hysteresis: std::cmp::max(opts.hysteresis.unwrap_or(1), 1),
+            consul: opts.consul,
+        })
+    }
+}
+
+/// The result of merging CLI flags, environment variables and an optional config file, in
+/// that order of precedence: CLI > env > file > built-in default.
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    pub watches: Vec<WatchConfig>,
+    pub health_addr: Option<String>,
+}
+
+impl RuntimeConfig {
+    /// Builds the final configuration for this run of the daemon. Called again on every SIGHUP
+    /// to produce the config the supervisor pushes into running detection tasks.
+    pub fn resolve(args: &Args) -> Result<Self> {
+        let file_opts = match &args.config {
+            Some(path) => ConfigOpts::load(path)?,
+            None => ConfigOpts::default(),
+        };
+        let env_opts = WatchOpts::from_env()?;
+        let cli_opts = WatchOpts::from_args(args);
+
+        let per_watch_opts = if file_opts.watches.is_empty() {
+            vec![file_opts.defaults.clone()]
+        } else {
+            file_opts.watches.clone()
+        };
+
+        let watches = per_watch_opts
+            .into_iter()
+            .map(|opts| file_opts.defaults.overlay(&opts).overlay(&env_opts).overlay(&cli_opts))
+            .map(WatchConfig::try_from)
+            .collect::<Result<Vec<_>>>()?;
+
+        if watches.is_empty() {
+            return Err(anyhow!("no watch target configured"));
+        }
+
+        let mut seen_ifaces = std::collections::HashSet::new();
+        for watch in &watches {
+            if !seen_ifaces.insert(watch.iface.clone()) {
+                return Err(anyhow!(
+                    "duplicate `iface` \"{}\" across [[watch]] entries: each watch target's \
+                     supervised task is keyed by `iface`, so it must be unique",
+                    watch.iface
+                ));
+            }
+        }
+
+        let mut seen_consul_health_addrs = std::collections::HashSet::new();
+        for watch in &watches {
+            if let Some(consul_config) = &watch.consul {
+                if !seen_consul_health_addrs.insert(consul_config.health_addr.clone()) {
+                    return Err(anyhow!(
+                        "duplicate Consul `health_addr` \"{}\" across [[watch]] entries: only the \
+                         first watch target to bind it would actually serve /health, the rest \
+                         would run silently degraded -- set a distinct `health_addr` per watch",
+                        consul_config.health_addr
+                    ));
+                }
+            }
+        }
+
+        let health_addr = args
+            .health_addr
+            .clone()
+            .or_else(|| env_var(ENV_PREFIX, "HEALTH_ADDR"))
+            .or(file_opts.health_addr);
+
+        Ok(Self { watches, health_addr })
+    }
+}
+
+fn env_var(prefix: &str, name: &str) -> Option<String> {
+    env::var(format!("{prefix}{name}")).ok()
+}
+
+fn env_parsed<T: FromStr>(prefix: &str, name: &str) -> Result<Option<T>>
+where
+    T::Err: std::fmt::Display,
+{
+    match env_var(prefix, name) {
+        Some(raw) => raw
+            .parse()
+            .map(Some)
+            .map_err(|e| anyhow!("invalid {prefix}{name}: {e}")),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts_with_targets(n: usize, quorum: Option<usize>) -> WatchOpts {
+        WatchOpts {
+            iface: Some("eth0".to_string()),
+            from: Some(IpAddr::from_str("192.0.2.1").unwrap()),
+            targets: (0..n).map(|_| ProbeConfig::Icmp { to: None }).collect(),
+            quorum,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn quorum_defaults_to_half_rounded_up_with_several_targets() {
+        let config = WatchConfig::try_from(opts_with_targets(4, None)).unwrap();
+        assert_eq!(config.quorum, 2);
+
+        let config = WatchConfig::try_from(opts_with_targets(5, None)).unwrap();
+        assert_eq!(config.quorum, 3);
+    }
+
+    #[test]
+    fn quorum_defaults_to_one_with_a_single_target() {
+        let config = WatchConfig::try_from(opts_with_targets(1, None)).unwrap();
+        assert_eq!(config.quorum, 1);
+    }
+
+    #[test]
+    fn explicit_quorum_is_capped_to_the_number_of_targets() {
+        let config = WatchConfig::try_from(opts_with_targets(3, Some(10))).unwrap();
+        assert_eq!(config.quorum, 3);
+    }
+
+    #[test]
+    fn explicit_quorum_is_respected_when_within_range() {
+        let config = WatchConfig::try_from(opts_with_targets(4, Some(1))).unwrap();
+        assert_eq!(config.quorum, 1);
+    }
+}
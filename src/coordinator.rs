@@ -0,0 +1,221 @@
+use anyhow::Result;
+use chrono::Utc;
+use log::{debug, error, info};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+use crate::config::ConsulConfig;
+
+const SESSION_TTL: Duration = Duration::from_secs(15);
+
+/// Cluster-visible state for a watch target: who's currently active and whether it's failed
+/// over to the NAT masquerade rule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterState {
+    pub active_node: String,
+    pub failover: bool,
+    pub transitioned_at: String,
+}
+
+/// Session-bound leader election and shared state publishing backed by Consul KV, so that two
+/// routers watching the same prefix don't both inject MASQUERADE (split-brain NAT). Only the
+/// elected leader is expected to call `inject_nat_masquerade`; followers just observe.
+pub struct ConsulCoordinator {
+    client: Client,
+    consul_url: String,
+    lock_key: String,
+    state_key: String,
+    node_id: String,
+    session_id: RwLock<Option<String>>,
+    is_leader: RwLock<bool>,
+}
+
+impl ConsulCoordinator {
+    pub fn new(config: &ConsulConfig) -> Self {
+        let prefix = config.key_prefix.trim_end_matches('/');
+        Self {
+            client: Client::new(),
+            consul_url: config.url.trim_end_matches('/').to_string(),
+            lock_key: format!("{prefix}/leader"),
+            state_key: format!("{prefix}/state"),
+            node_id: config
+                .node_id
+                .clone()
+                .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().into_owned()),
+            session_id: RwLock::new(None),
+            is_leader: RwLock::new(false),
+        }
+    }
+
+    async fn ensure_session(&self) -> Result<String> {
+        if let Some(id) = self.session_id.read().await.clone() {
+            return Ok(id);
+        }
+
+        #[derive(Deserialize)]
+        struct SessionResp {
+            #[serde(rename = "ID")]
+            id: String,
+        }
+
+        let resp: SessionResp = self
+            .client
+            .put(format!("{}/v1/session/create", self.consul_url))
+            .json(&json!({
+                "Name": format!("nat-failover-{}", self.node_id),
+                "TTL": format!("{}s", SESSION_TTL.as_secs()),
+                "Behavior": "release",
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        *self.session_id.write().await = Some(resp.id.clone());
+        Ok(resp.id)
+    }
+
+    /// Renews the session and attempts to acquire (or keep) the leader lock. Returns whether
+    /// this node currently holds it.
+    pub async fn try_become_leader(&self) -> Result<bool> {
+        let mut session = self.ensure_session().await?;
+
+        match self
+            .client
+            .put(format!("{}/v1/session/renew/{}", self.consul_url, session))
+            .send()
+            .await
+        {
+            // A 404 means Consul itself no longer knows this session (it expired or was
+            // destroyed server-side) -- only then is it actually safe to create a new one.
+            Ok(resp) if resp.status() == reqwest::StatusCode::NOT_FOUND => {
+                debug!("Consul session {} no longer exists, creating a new one.", session);
+                *self.session_id.write().await = None;
+                session = self.ensure_session().await?;
+            }
+            Ok(resp) => {
+                resp.error_for_status()?;
+            }
+            Err(err) => {
+                // A transport error (dropped connection, timeout, ...) doesn't mean the
+                // session is gone -- it may still be held server-side. Recreating it here
+                // would make Consul refuse to hand the lock to the new session while the old
+                // one is still valid, so this node would spuriously lose leadership for
+                // nothing worse than a blip. Just skip this round and retry next tick.
+                return Err(err.into());
+            }
+        }
+
+        self.acquire_lock(&session).await
+    }
+
+    async fn acquire_lock(&self, session: &str) -> Result<bool> {
+        let acquired: bool = self
+            .client
+            .put(format!("{}/v1/kv/{}?acquire={}", self.consul_url, self.lock_key, session))
+            .body(self.node_id.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        *self.is_leader.write().await = acquired;
+        Ok(acquired)
+    }
+
+    pub async fn is_leader(&self) -> bool {
+        *self.is_leader.read().await
+    }
+
+    /// Publishes the cluster-visible state: who's active and the current failover status.
+    pub async fn publish_state(&self, failover: bool) -> Result<()> {
+        let state = ClusterState {
+            active_node: self.node_id.clone(),
+            failover,
+            transitioned_at: Utc::now().to_rfc3339(),
+        };
+
+        self.client
+            .put(format!("{}/v1/kv/{}", self.consul_url, self.state_key))
+            .json(&state)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Releases the lock and destroys the session so the peer can take over immediately,
+    /// instead of waiting out the session TTL. Called on shutdown.
+    pub async fn release(&self) -> Result<()> {
+        *self.is_leader.write().await = false;
+
+        let Some(session) = self.session_id.write().await.take() else {
+            return Ok(());
+        };
+
+        if let Err(err) = self
+            .client
+            .put(format!("{}/v1/kv/{}?release={}", self.consul_url, self.lock_key, session))
+            .send()
+            .await
+        {
+            error!("Error releasing the Consul lock: {}", err);
+        }
+
+        if let Err(err) = self
+            .client
+            .put(format!("{}/v1/session/destroy/{}", self.consul_url, session))
+            .send()
+            .await
+        {
+            error!("Error destroying the Consul session: {}", err);
+        }
+
+        Ok(())
+    }
+}
+
+/// Serves a minimal `GET /health` endpoint reporting whether this node currently holds
+/// leadership for its watch target.
+pub async fn serve_health(addr: SocketAddr, coordinator: Arc<ConsulCoordinator>) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Health endpoint listening on {}", addr);
+
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let coordinator = coordinator.clone();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            if socket.read(&mut buf).await.is_err() {
+                return;
+            }
+
+            let body = json!({
+                "leader": coordinator.is_leader().await,
+                "node": coordinator.node_id,
+            })
+            .to_string();
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+
+            if let Err(err) = socket.write_all(response.as_bytes()).await {
+                debug!("Error writing health response: {}", err);
+            }
+        });
+    }
+}
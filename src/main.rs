@@ -1,50 +1,82 @@
 use anyhow::{anyhow, Result};
 use clap::Parser;
-use futures::{pin_mut, stream::StreamExt};
 use iptables::IPTables;
 use log::{debug, error, info};
-use netdiag::{Bind, Ping, Pinger};
-use std::net::IpAddr;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{watch, RwLock};
 use tokio::time::sleep;
 
-mod alerts;
+mod config;
+mod coordinator;
+mod notifiers;
+mod probes;
+mod supervisor;
 
-/// NAT failover detects a failure in the routing of an IPv6 block through DHCP-PD by testing
-/// pinging an address from the default IP and an IP supposed to be routed.
+use config::{ConsulConfig, RuntimeConfig, WatchConfig};
+use coordinator::ConsulCoordinator;
+use notifiers::{FailoverEvent, Notifier};
+use probes::Probe;
+use supervisor::{StateMap, TargetState};
+
+/// NAT failover detects a failure in the routing of an IPv6 block through DHCP-PD by probing
+/// an address from the default IP and an IP supposed to be routed.
 /// If the first one works but the second one fails, then injects a NAT MASQUERADE rule to temporarily NAT the IPv6
 /// traffic until the block is routed again.
+///
+/// A watch target can be fully described on the command line, or read from a `--config` file
+/// (which may list several `[[watch]]` entries to supervise more than one interface/prefix at
+/// once). CLI flags and `NAT_FAILOVER_*` environment variables take precedence over the file.
+///
+/// A supervisor spawns one detection task per watch target and keeps them running
+/// independently; sending the process SIGHUP reloads the configuration and pushes it to every
+/// task, so intervals, targets and thresholds can change without dropping NAT rules already in
+/// place.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about=None, max_term_width = 100)]
 struct Args {
+    /// Path to a TOML or YAML configuration file.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
     /// WAN interface.
     #[arg(short, long)]
-    iface: String,
+    iface: Option<String>,
 
     /// IP to bind on.
     #[arg(short, long)]
-    from: IpAddr,
+    from: Option<IpAddr>,
 
     /// IP to ping.
-    #[arg(short, long, default_value = "2001:4860:4860::8888")]
-    to: IpAddr,
+    #[arg(short, long)]
+    to: Option<IpAddr>,
 
     /// Retries.
-    #[arg(short, long, default_value = "5")]
-    retries: usize,
+    #[arg(short, long)]
+    retries: Option<usize>,
 
     /// Timeout.
-    #[arg(long, default_value = "500", value_parser = |arg: &str| -> Result<Duration, ParseIntError> {Ok(Duration::from_millis(arg.parse()?))})]
-    timeout: Duration,
+    #[arg(long, value_parser = |arg: &str| -> Result<Duration, ParseIntError> {Ok(Duration::from_millis(arg.parse()?))})]
+    timeout: Option<Duration>,
 
     /// Interval in seconds for the testing and apply or clean the failover.
-    #[arg(long, default_value = "15", value_parser = |arg: &str| -> Result<Duration, ParseIntError> {Ok(Duration::from_secs(arg.parse()?))})]
-    interval: Duration,
+    #[arg(long, value_parser = |arg: &str| -> Result<Duration, ParseIntError> {Ok(Duration::from_secs(arg.parse()?))})]
+    interval: Option<Duration>,
 
-    /// Alertmanager URL.
+    /// Alertmanager URL. Shorthand for a single Alertmanager notifier; for other notifier
+    /// types (webhook, shell command) or several notifiers at once, use `--config`.
     #[arg(short, long)]
-    alertmanager_url: String,
+    alertmanager_url: Option<String>,
+
+    /// Address the supervisor's aggregated `/health` endpoint listens on, e.g. `127.0.0.1:8081`.
+    /// Unset disables it.
+    #[arg(long)]
+    health_addr: Option<String>,
 }
 
 #[tokio::main]
@@ -52,101 +84,327 @@ async fn main() -> Result<()> {
     let args = Args::parse();
     env_logger::init();
 
-    let l = DetectionLoop::new(args).await?;
-    l.run().await;
+    let runtime_config = RuntimeConfig::resolve(&args)?;
+    let health_addr = runtime_config.health_addr.clone();
+
+    let (tx, rx) = watch::channel(runtime_config);
+    tokio::spawn(reload_on_sighup(args, tx));
+
+    let states: StateMap = Arc::new(RwLock::new(HashMap::new()));
+
+    if let Some(addr) = health_addr {
+        let addr: SocketAddr = addr.parse()?;
+        let states = states.clone();
+        tokio::spawn(async move {
+            if let Err(err) = supervisor::serve_health(addr, states).await {
+                error!("Supervisor health endpoint stopped: {}", err);
+            }
+        });
+    }
+
+    supervisor::run(rx, states).await;
 
     Ok(())
 }
 
+/// Re-resolves the configuration from the file/env/CLI on every SIGHUP and pushes it to the
+/// supervisor, which forwards it to every running detection task.
+async fn reload_on_sighup(args: Args, tx: watch::Sender<RuntimeConfig>) {
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(err) => {
+            error!("Could not install SIGHUP handler: {}", err);
+            return;
+        }
+    };
+
+    while sighup.recv().await.is_some() {
+        info!("Received SIGHUP, reloading configuration.");
+        match RuntimeConfig::resolve(&args) {
+            Ok(config) => {
+                if tx.send(config).is_err() {
+                    return;
+                }
+            }
+            Err(err) => error!("Error reloading configuration: {}", err),
+        }
+    }
+}
+
+/// Owns one watch target end-to-end: its probes, its `IPTables` handle, its notifiers and its
+/// Consul coordinator. Runs as its own supervised task, watching the shared `RuntimeConfig`
+/// channel so a reload (SIGHUP) can update its probes/notifiers/thresholds in place without
+/// dropping a NAT rule already applied.
 struct DetectionLoop {
-    args: Args,
-    ping_opts: Ping,
-    pinger_default: Pinger,
-    pinger_test_from: Pinger,
+    config: WatchConfig,
+    probes: Vec<Box<dyn Probe>>,
     iptables_client: IPTables,
-}
+    notifiers: Vec<Box<dyn Notifier>>,
+    coordinator: Option<Arc<ConsulCoordinator>>,
+    rx: watch::Receiver<RuntimeConfig>,
+    states: StateMap,
 
-impl DetectionLoop {
-    pub async fn new(args: Args) -> Result<Self> {
-        let ping_opts = Ping {
-            addr: args.to,
-            count: std::cmp::max(args.retries, 1),
-            expiry: args.timeout,
-        };
+    // Sets the NAT switch to force a clean-up of the NAT rule if the first quorum check
+    // succeeds.
+    nat_switch: bool,
 
-        let mut bind_test_from = Bind::default();
-        bind_test_from.set(args.from);
+    // Tracks hysteresis: the decision (should-NAT or not) the last few loops agreed on, and
+    // for how many consecutive loops, so a single noisy cycle can't flip `nat_switch`.
+    pending_decision: Option<bool>,
+    pending_count: usize,
+}
 
-        let pinger_default = Pinger::new(&Bind::default()).await?;
-        let pinger_test_from = Pinger::new(&bind_test_from).await?;
+impl DetectionLoop {
+    pub async fn new(config: WatchConfig, rx: watch::Receiver<RuntimeConfig>, states: StateMap) -> Result<Self> {
+        let probes = probes::build(&config)?;
 
         let mut iptables_client = iptables::new(true).unwrap();
         iptables_client.set_numeric(true);
 
+        let notifiers = notifiers::build(&config.notifiers)?;
+        let coordinator = match &config.consul {
+            Some(consul_config) => Some(Self::spawn_coordinator(consul_config)?),
+            None => None,
+        };
+
         Ok(Self {
-            args,
-            ping_opts,
-            pinger_default,
-            pinger_test_from,
+            config,
+            probes,
             iptables_client,
+            notifiers,
+            coordinator,
+            rx,
+            states,
+            nat_switch: true,
+            pending_decision: None,
+            pending_count: 0,
         })
     }
 
-    pub async fn run(&self) {
-        // Sets the NAT switch to force a clean-up of the NAT rule if the first ping succeeds.
-        let mut nat_switch = true;
-        let mut alert = alerts::Alert::new();
+    fn spawn_coordinator(consul_config: &ConsulConfig) -> Result<Arc<ConsulCoordinator>> {
+        let coordinator = Arc::new(ConsulCoordinator::new(consul_config));
+        let health_addr = consul_config.health_addr.parse()?;
+        let for_health = coordinator.clone();
+        tokio::spawn(async move {
+            if let Err(err) = coordinator::serve_health(health_addr, for_health).await {
+                error!("Health endpoint stopped: {}", err);
+            }
+        });
+        Ok(coordinator)
+    }
 
+    pub async fn run(&mut self) {
         loop {
-            let future_default = ping(&self.args, &self.pinger_default, &self.ping_opts);
-            let future_test_from = ping(&self.args, &self.pinger_test_from, &self.ping_opts);
-
-            match future_default.await {
-                Ok(_) => {
-                    debug!("Ping from default IP succeeded, trying from IP {}...", self.args.from);
-                    match future_test_from.await {
-                        Err(_) => {
-                            let msg = format!(
-                                    "Ping from IP {} failed after {} retries. Adding the NAT masquerade rule.",
-                                    self.args.from,
-                                    self.args.retries,
-                                );
-                            if !nat_switch {
-                                // Only logs in INFO if the NAT switch was off, to not flood the
-                                // logs at every loop.
-                                info!("{}", msg);
-                            } else {
-                                debug!("{}", msg);
-                            }
+            self.check_and_act().await;
+
+            tokio::select! {
+                _ = sleep(self.config.interval) => {}
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Shutting down, cleaning up the NAT rule.");
+                    self.shutdown().await;
+                    return;
+                }
+                changed = self.rx.changed() => {
+                    if changed.is_err() {
+                        self.shutdown().await;
+                        return;
+                    }
+
+                    let new_runtime = self.rx.borrow().clone();
+                    match new_runtime.watches.into_iter().find(|w| w.iface == self.config.iface) {
+                        Some(new_config) => self.reconfigure(new_config).await,
+                        None => {
+                            info!("Watch target {} removed from config, shutting down.", self.config.iface);
+                            self.shutdown().await;
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn check_and_act(&mut self) {
+        // Unspecified address matching `self.config.from`'s family: tells the probes to let
+        // the OS pick the route, i.e. "the default IP". Recomputed every call (rather than
+        // once in `run`) so a reload that switches `from`'s address family takes effect
+        // immediately instead of probing with a stale family's unspecified address.
+        let default_bind = match self.config.from {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+
+        let is_leader = self.is_leader().await;
+        let default_check = probes::check_quorum(&self.probes, self.config.quorum, default_bind);
+        let test_from_check = probes::check_quorum(&self.probes, self.config.quorum, self.config.from);
+
+        match default_check.await {
+            Ok(_) => {
+                debug!("Quorum reached from default IP, trying from IP {}...", self.config.from);
+                let should_nat = test_from_check.await.is_err();
+
+                if self.pending_decision == Some(should_nat) {
+                    self.pending_count += 1;
+                } else {
+                    self.pending_decision = Some(should_nat);
+                    self.pending_count = 1;
+                }
+
+                if self.pending_count < self.config.hysteresis {
+                    debug!(
+                        "Decision (should_nat={}) held for {}/{} loops, waiting for hysteresis.",
+                        should_nat, self.pending_count, self.config.hysteresis
+                    );
+                } else {
+                    // Only the leader publishes, since the state names the publisher as the
+                    // active node.
+                    if let Some(coordinator) = self.coordinator.as_ref().filter(|_| is_leader) {
+                        if let Err(err) = coordinator.publish_state(should_nat).await {
+                            error!("Error publishing cluster state to Consul: {}", err);
+                        }
+                    }
+
+                    if should_nat {
+                        let msg = format!(
+                            "Quorum not reached from IP {}. Adding the NAT masquerade rule.",
+                            self.config.from,
+                        );
+                        if !self.nat_switch {
+                            // Only logs in INFO if the NAT switch was off, to not flood the
+                            // logs at every loop.
+                            info!("{}", msg);
+                        } else {
+                            debug!("{}", msg);
+                        }
+                        if !is_leader {
+                            debug!("Not the elected leader, observing without touching the NAT rule.");
+                        } else {
                             match self.inject_nat_masquerade() {
                                 Err(err) => error!("Error adding the NAT rule: {}", err),
                                 Ok(_) => {
-                                    nat_switch = true;
-                                    alert.trigger(&self.args.alertmanager_url).await;
+                                    self.nat_switch = true;
+                                    self.notify_trigger("quorum of probes from the routed IP failed").await;
                                 }
                             }
                         }
-                        _ => {
-                            debug!("Ping from IP {} succeeded.", self.args.from,);
-                            if nat_switch {
-                                info!("Cleanup the NAT masquerade rule if existing.");
-                                match self.cleanup_nat_masquerade() {
-                                    Err(err) => error!("Error cleaning the NAT rule: {}", err),
-                                    Ok(_) => {
-                                        nat_switch = false;
-                                        alert.resolve(&self.args.alertmanager_url).await;
-                                    }
+                    } else {
+                        debug!("Quorum reached from IP {}.", self.config.from);
+                        if self.nat_switch && !is_leader {
+                            debug!("Not the elected leader, observing without touching the NAT rule.");
+                        } else if self.nat_switch {
+                            info!("Cleanup the NAT masquerade rule if existing.");
+                            match self.cleanup_nat_masquerade() {
+                                Err(err) => error!("Error cleaning the NAT rule: {}", err),
+                                Ok(_) => {
+                                    self.nat_switch = false;
+                                    self.notify_resolve("quorum of probes from the routed IP recovered").await;
                                 }
                             }
                         }
-                    };
+                    }
                 }
-                _ => info!(
-                    "Ping from the default IP failed after {} retries. Not taking action as the WAN seems to be under problems.",
-                    self.args.retries
-                ),
-            };
-            sleep(self.args.interval).await;
+            }
+            _ => {
+                info!(
+                    "Quorum not reached from the default IP. Not taking action as the WAN seems to be under problems."
+                );
+                self.pending_decision = None;
+                self.pending_count = 0;
+            }
+        };
+
+        self.publish_state(is_leader).await;
+    }
+
+    /// Rebuilds probes and notifiers from the reloaded config, and replaces the coordinator
+    /// only if Consul coordination was turned on or off -- never mid-session, so a held lock or
+    /// an applied NAT rule is never disturbed by a reload.
+    async fn reconfigure(&mut self, new_config: WatchConfig) {
+        info!("Reloading configuration for watch target {}.", new_config.iface);
+
+        match probes::build(&new_config) {
+            Ok(probes) => self.probes = probes,
+            Err(err) => error!("Error rebuilding probes for {}: {}", new_config.iface, err),
+        }
+
+        match notifiers::build(&new_config.notifiers) {
+            Ok(notifiers) => self.notifiers = notifiers,
+            Err(err) => error!("Error rebuilding notifiers for {}: {}", new_config.iface, err),
+        }
+
+        match (&self.coordinator, &new_config.consul) {
+            (None, Some(consul_config)) => match Self::spawn_coordinator(consul_config) {
+                Ok(coordinator) => self.coordinator = Some(coordinator),
+                Err(err) => error!("Error starting Consul coordination for {}: {}", new_config.iface, err),
+            },
+            (Some(_), None) => {
+                info!(
+                    "Consul coordination removed for {}, this node now always acts alone.",
+                    new_config.iface
+                );
+                self.coordinator = None;
+            }
+            _ => {}
+        }
+
+        self.config = new_config;
+    }
+
+    async fn shutdown(&self) {
+        if self.nat_switch && self.is_leader().await {
+            match self.cleanup_nat_masquerade() {
+                Err(err) => error!("Error cleaning the NAT rule during shutdown: {}", err),
+                Ok(_) => self.notify_resolve("shutting down, cleaning up the NAT rule").await,
+            }
+        }
+        if let Some(coordinator) = &self.coordinator {
+            if let Err(err) = coordinator.release().await {
+                error!("Error releasing the Consul lock during shutdown: {}", err);
+            }
+        }
+        self.states.write().await.remove(&self.config.iface);
+    }
+
+    /// Whether this node is allowed to mutate the NAT rule: always true unless Consul
+    /// coordination is configured, in which case only the elected leader may act.
+    async fn is_leader(&self) -> bool {
+        match &self.coordinator {
+            Some(coordinator) => coordinator.try_become_leader().await.unwrap_or(false),
+            None => true,
+        }
+    }
+
+    async fn publish_state(&self, is_leader: bool) {
+        self.states.write().await.insert(
+            self.config.iface.clone(),
+            TargetState {
+                iface: self.config.iface.clone(),
+                from: self.config.from.to_string(),
+                nat_active: self.nat_switch,
+                leader: is_leader,
+            },
+        );
+    }
+
+    async fn notify_trigger(&self, reason: &str) {
+        let event = self.failover_event(reason);
+        for notifier in &self.notifiers {
+            notifier.trigger(&event).await;
+        }
+    }
+
+    async fn notify_resolve(&self, reason: &str) {
+        let event = self.failover_event(reason);
+        for notifier in &self.notifiers {
+            notifier.resolve(&event).await;
+        }
+    }
+
+    fn failover_event(&self, reason: &str) -> FailoverEvent {
+        FailoverEvent {
+            iface: self.config.iface.clone(),
+            from: self.config.from,
+            reason: reason.to_string(),
         }
     }
 
@@ -154,14 +412,14 @@ impl DetectionLoop {
         match self.iptables_client.exists(
             "nat",
             "POSTROUTING",
-            masquerade_rule(self.args.iface.as_str(), self.args.from).as_str(),
+            masquerade_rule(self.config.iface.as_str(), self.config.from).as_str(),
         ) {
             Ok(v) => {
                 if !v {
                     match self.iptables_client.insert_unique(
                         "nat",
                         "POSTROUTING",
-                        masquerade_rule(self.args.iface.as_str(), self.args.from).as_str(),
+                        masquerade_rule(self.config.iface.as_str(), self.config.from).as_str(),
                         1,
                     ) {
                         Ok(i) => return Ok(i),
@@ -179,14 +437,14 @@ impl DetectionLoop {
         match self.iptables_client.exists(
             "nat",
             "POSTROUTING",
-            masquerade_rule(self.args.iface.as_str(), self.args.from).as_str(),
+            masquerade_rule(self.config.iface.as_str(), self.config.from).as_str(),
         ) {
             Ok(v) => {
                 if v {
                     match self.iptables_client.delete(
                         "nat",
                         "POSTROUTING",
-                        masquerade_rule(self.args.iface.as_str(), self.args.from).as_str(),
+                        masquerade_rule(self.config.iface.as_str(), self.config.from).as_str(),
                     ) {
                         Ok(i) => return Ok(i),
                         Err(e) => return Err(anyhow!(format!("{:?}", e))),
@@ -200,33 +458,6 @@ impl DetectionLoop {
     }
 }
 
-async fn ping(args: &Args, pinger: &Pinger, ping_opts: &Ping) -> Result<()> {
-    let stream = pinger.ping(ping_opts).enumerate();
-    pin_mut!(stream);
-
-    let mut count = 0;
-    let mut errors = 0;
-    while let Some((_, item)) = stream.next().await {
-        match item? {
-            Some(_) => return Ok(()),
-            None => {
-                errors += 1;
-            }
-        }
-
-        if errors >= args.retries {
-            return Err(anyhow!("number of errors {} exceeded", errors));
-        }
-
-        count += 1;
-        if count < ping_opts.count {
-            sleep(Duration::from_millis(500)).await;
-        }
-    }
-
-    Ok(())
-}
-
 fn masquerade_rule(iface: &str, exclude_ip: IpAddr) -> String {
     format!("-o {} ! -s {} -j MASQUERADE", iface, exclude_ip)
 }
@@ -0,0 +1,109 @@
+use super::{FailoverEvent, Notifier};
+use async_trait::async_trait;
+use chrono::Utc;
+use gethostname::gethostname;
+use log::{debug, error};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Posts Alertmanager-shaped alerts to `{url}/api/v1/alerts` for failover transitions.
+pub struct AlertmanagerNotifier {
+    url: String,
+    state: Mutex<AlertState>,
+    client: Client,
+}
+
+#[derive(Default)]
+struct AlertState {
+    starts_at: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Alert {
+    labels: HashMap<String, String>,
+
+    annotations: HashMap<String, String>,
+
+    #[serde(rename = "startsAt")]
+    starts_at: Option<String>,
+
+    #[serde(rename = "endsAt")]
+    ends_at: Option<String>,
+}
+
+impl AlertmanagerNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            state: Mutex::new(AlertState::default()),
+            client: Client::new(),
+        }
+    }
+
+    fn alert(&self, event: &FailoverEvent, starts_at: Option<String>, ends_at: Option<String>) -> Alert {
+        Alert {
+            labels: HashMap::from([("alertname".into(), format!("NAT enabled on {:?}", gethostname()))]),
+            annotations: HashMap::from([(
+                "description".into(),
+                format!(
+                    "NAT enabled as fallback for routing problem on {} (from {})",
+                    event.iface, event.from
+                ),
+            )]),
+            starts_at,
+            ends_at,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for AlertmanagerNotifier {
+    async fn trigger(&self, event: &FailoverEvent) {
+        let mut state = self.state.lock().await;
+        if state.starts_at.is_none() {
+            state.starts_at = Some(Utc::now().to_rfc3339());
+        }
+
+        debug!("Sending alert.");
+        let alert = self.alert(event, state.starts_at.clone(), None);
+        post_alert(&self.client, &self.url, &alert).await;
+    }
+
+    async fn resolve(&self, event: &FailoverEvent) {
+        let mut state = self.state.lock().await;
+        // Only resolve the alert if it was triggered.
+        if state.starts_at.is_none() {
+            return;
+        }
+
+        debug!("Resolving the alert.");
+        let alert = self.alert(event, state.starts_at.clone(), Some(Utc::now().to_rfc3339()));
+        post_alert(&self.client, &self.url, &alert).await;
+
+        state.starts_at = None;
+    }
+}
+
+async fn post_alert(client: &Client, url: &str, alert: &Alert) {
+    let alerts = vec![alert];
+    debug!("Posting alerts {:?}", alerts);
+
+    let res = match client
+        .post(format!("{}/api/v1/alerts", url))
+        .json(&alerts)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+    {
+        Ok(res) => res,
+        Err(err) => return error!("Error posting the alert: {}", err),
+    };
+
+    match res.error_for_status() {
+        Ok(text) => debug!("Alertmanager response: {}", text.text().await.unwrap()),
+        Err(err) => error!("Alertmanager returned an error: {}", err),
+    }
+}
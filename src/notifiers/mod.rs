@@ -0,0 +1,51 @@
+use crate::config::NotifierConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::net::IpAddr;
+
+pub mod alertmanager;
+pub mod shell;
+pub mod webhook;
+
+pub use alertmanager::AlertmanagerNotifier;
+pub use shell::ShellNotifier;
+pub use webhook::WebhookNotifier;
+
+/// A failover notification: the routed path is down (the NAT masquerade rule was injected or
+/// is still in place) or the rule was removed.
+#[derive(Debug, Clone)]
+pub struct FailoverEvent {
+    pub iface: String,
+    pub from: IpAddr,
+    pub reason: String,
+}
+
+/// Something that can be told about a failover transition. Implementations decide how to
+/// turn `trigger`/`resolve` into an actual notification (HTTP call, shell command, ...).
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Called after every check that finds the routed path down and the NAT masquerade rule
+    /// in place, not only the first one, so it repeats for as long as the failover lasts.
+    async fn trigger(&self, event: &FailoverEvent);
+
+    /// Called when the NAT masquerade rule is cleaned up: the routed path recovered, or the
+    /// daemon is shutting down. The first check after startup that finds the routed path up
+    /// also calls it, whether or not a rule was in place.
+    async fn resolve(&self, event: &FailoverEvent);
+}
+
+/// Builds the notifiers configured for a watch target.
+pub fn build(configs: &[NotifierConfig]) -> Result<Vec<Box<dyn Notifier>>> {
+    Ok(configs
+        .iter()
+        .map(|c| -> Box<dyn Notifier> {
+            match c {
+                NotifierConfig::Alertmanager { url } => Box::new(AlertmanagerNotifier::new(url.clone())),
+                NotifierConfig::Webhook { url, method, body_template } => {
+                    Box::new(WebhookNotifier::new(url.clone(), method.clone(), body_template.clone()))
+                }
+                NotifierConfig::Shell { command } => Box::new(ShellNotifier::new(command.clone())),
+            }
+        })
+        .collect())
+}
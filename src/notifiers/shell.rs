@@ -0,0 +1,48 @@
+use super::{FailoverEvent, Notifier};
+use async_trait::async_trait;
+use log::error;
+use tokio::process::Command;
+
+/// Runs a shell command on every trigger and resolve. The command is invoked through `sh -c` with
+/// the event exposed as environment variables: `NAT_FAILOVER_IFACE`, `NAT_FAILOVER_FROM`,
+/// `NAT_FAILOVER_STATUS` (`firing` or `resolved`) and `NAT_FAILOVER_REASON`.
+pub struct ShellNotifier {
+    command: String,
+}
+
+impl ShellNotifier {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+
+    async fn run(&self, event: &FailoverEvent, status: &str) {
+        let result = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("NAT_FAILOVER_IFACE", &event.iface)
+            .env("NAT_FAILOVER_FROM", event.from.to_string())
+            .env("NAT_FAILOVER_STATUS", status)
+            .env("NAT_FAILOVER_REASON", &event.reason)
+            .status()
+            .await;
+
+        match result {
+            Ok(status) if !status.success() => {
+                error!("Notifier command `{}` exited with {}", self.command, status)
+            }
+            Err(err) => error!("Error running notifier command `{}`: {}", self.command, err),
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for ShellNotifier {
+    async fn trigger(&self, event: &FailoverEvent) {
+        self.run(event, "firing").await;
+    }
+
+    async fn resolve(&self, event: &FailoverEvent) {
+        self.run(event, "resolved").await;
+    }
+}
@@ -0,0 +1,84 @@
+use super::{FailoverEvent, Notifier};
+use async_trait::async_trait;
+use log::{debug, error, warn};
+use reqwest::{Client, Method};
+use std::time::Duration;
+
+/// Posts a JSON payload to an arbitrary URL on every trigger and resolve. `body_template` may
+/// reference `{{iface}}`, `{{from}}`, `{{status}}` and `{{reason}}`, substituted verbatim; if
+/// unset, a small default JSON body is sent.
+pub struct WebhookNotifier {
+    url: String,
+    method: String,
+    body_template: Option<String>,
+    client: Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String, method: String, body_template: Option<String>) -> Self {
+        Self {
+            url,
+            method,
+            body_template,
+            client: Client::new(),
+        }
+    }
+
+    fn render(&self, event: &FailoverEvent, status: &str) -> String {
+        let template = self.body_template.clone().unwrap_or_else(|| {
+            r#"{"iface":"{{iface}}","from":"{{from}}","status":"{{status}}","reason":"{{reason}}"}"#.to_string()
+        });
+
+        template
+            .replace("{{iface}}", &event.iface)
+            .replace("{{from}}", &event.from.to_string())
+            .replace("{{status}}", status)
+            .replace("{{reason}}", &event.reason)
+    }
+
+    async fn send(&self, event: &FailoverEvent, status: &str) {
+        let body = self.render(event, status);
+        debug!("Posting webhook to {}: {}", self.url, body);
+
+        let method = match self.method.to_uppercase().as_str() {
+            "GET" => Method::GET,
+            "POST" => Method::POST,
+            "PUT" => Method::PUT,
+            "PATCH" => Method::PATCH,
+            "DELETE" => Method::DELETE,
+            "HEAD" => Method::HEAD,
+            other => {
+                warn!("Unknown webhook method \"{}\", falling back to POST.", other);
+                Method::POST
+            }
+        };
+
+        let res = match self
+            .client
+            .request(method, &self.url)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => return error!("Error posting the webhook: {}", err),
+        };
+
+        if let Err(err) = res.error_for_status() {
+            error!("Webhook returned an error: {}", err);
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn trigger(&self, event: &FailoverEvent) {
+        self.send(event, "firing").await;
+    }
+
+    async fn resolve(&self, event: &FailoverEvent) {
+        self.send(event, "resolved").await;
+    }
+}
@@ -0,0 +1,73 @@
+use super::Probe;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Verifies a service is reachable by issuing a GET request and checking the response status
+/// falls within `[min_status, max_status]`.
+///
+/// `check` is called with a handful of distinct `bind` addresses at most (the default route
+/// and its watch target's `from`), so the `Client` for each is built once and cached here
+/// instead of opening a fresh connection pool on every call.
+pub struct HttpProbe {
+    url: String,
+    min_status: u16,
+    max_status: u16,
+    timeout: Duration,
+    clients: Mutex<HashMap<IpAddr, Client>>,
+}
+
+impl HttpProbe {
+    pub fn new(url: String, min_status: u16, max_status: u16, timeout: Duration) -> Self {
+        Self {
+            url,
+            min_status,
+            max_status,
+            timeout,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn client_for(&self, bind: IpAddr) -> Result<Client> {
+        let mut builder = Client::builder().timeout(self.timeout);
+        if !bind.is_unspecified() {
+            builder = builder.local_address(bind);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+#[async_trait]
+impl Probe for HttpProbe {
+    async fn check(&self, bind: IpAddr) -> Result<()> {
+        let client = {
+            let mut clients = self.clients.lock().await;
+            if let Some(client) = clients.get(&bind) {
+                client.clone()
+            } else {
+                let client = self.client_for(bind)?;
+                clients.insert(bind, client.clone());
+                client
+            }
+        };
+
+        let res = client.get(&self.url).send().await?;
+        let status = res.status().as_u16();
+
+        if status < self.min_status || status > self.max_status {
+            return Err(anyhow!(
+                "{} returned status {}, expected {}-{}",
+                self.url,
+                status,
+                self.min_status,
+                self.max_status
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use super::Probe;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{pin_mut, stream::StreamExt};
+use netdiag::{Bind, Ping, Pinger};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+/// The original reachability check: pings `to` and retries until `retries` ping errors have
+/// been seen or one reply comes back.
+///
+/// `check` is called with a handful of distinct `bind` addresses at most (the default route
+/// and its watch target's `from`), so the `Pinger` for each is opened once and cached here
+/// instead of opening a fresh raw socket on every call.
+pub struct IcmpProbe {
+    to: IpAddr,
+    retries: usize,
+    timeout: Duration,
+    pingers: Mutex<HashMap<IpAddr, Arc<Pinger>>>,
+}
+
+impl IcmpProbe {
+    pub fn new(to: IpAddr, retries: usize, timeout: Duration) -> Self {
+        Self {
+            to,
+            retries,
+            timeout,
+            pingers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn pinger_for(&self, bind: IpAddr) -> Result<Arc<Pinger>> {
+        let mut pingers = self.pingers.lock().await;
+        if let Some(pinger) = pingers.get(&bind) {
+            return Ok(pinger.clone());
+        }
+
+        let mut bind_opts = Bind::default();
+        if !bind.is_unspecified() {
+            bind_opts.set(bind);
+        }
+        let pinger = Arc::new(Pinger::new(&bind_opts).await?);
+        pingers.insert(bind, pinger.clone());
+        Ok(pinger)
+    }
+}
+
+#[async_trait]
+impl Probe for IcmpProbe {
+    async fn check(&self, bind: IpAddr) -> Result<()> {
+        let pinger = self.pinger_for(bind).await?;
+
+        let ping_opts = Ping {
+            addr: self.to,
+            count: std::cmp::max(self.retries, 1),
+            expiry: self.timeout,
+        };
+
+        let stream = pinger.ping(&ping_opts).enumerate();
+        pin_mut!(stream);
+
+        let mut count = 0;
+        let mut errors = 0;
+        while let Some((_, item)) = stream.next().await {
+            match item? {
+                Some(_) => return Ok(()),
+                None => {
+                    errors += 1;
+                }
+            }
+
+            if errors >= self.retries {
+                return Err(anyhow!("number of errors {} exceeded", errors));
+            }
+
+            count += 1;
+            if count < ping_opts.count {
+                sleep(Duration::from_millis(500)).await;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::config::{ProbeConfig, WatchConfig};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use std::net::IpAddr;
+
+pub mod http;
+pub mod icmp;
+pub mod tcp;
+
+pub use http::HttpProbe;
+pub use icmp::IcmpProbe;
+pub use tcp::TcpConnectProbe;
+
+/// A reachability check that can be run from a given source address.
+///
+/// `bind` is the source IP to probe from. An unspecified address (`0.0.0.0` / `::`) means
+/// "let the OS pick the route", matching the usual default-route behavior; any other address
+/// means the probe must originate from that specific IP.
+#[async_trait]
+pub trait Probe: Send + Sync {
+    async fn check(&self, bind: IpAddr) -> Result<()>;
+}
+
+fn build_one(target: &ProbeConfig, config: &WatchConfig) -> Box<dyn Probe> {
+    match target {
+        ProbeConfig::Icmp { to } => Box::new(IcmpProbe::new(
+            to.unwrap_or(config.to),
+            config.retries,
+            config.timeout,
+        )),
+        ProbeConfig::TcpConnect { host, port } => {
+            Box::new(TcpConnectProbe::new(host.clone(), *port, config.timeout))
+        }
+        ProbeConfig::Http {
+            url,
+            min_status,
+            max_status,
+        } => Box::new(HttpProbe::new(url.clone(), *min_status, *max_status, config.timeout)),
+    }
+}
+
+/// Builds the probes configured for a watch target, one per entry in `config.targets`.
+pub fn build(config: &WatchConfig) -> Result<Vec<Box<dyn Probe>>> {
+    Ok(config.targets.iter().map(|t| build_one(t, config)).collect())
+}
+
+/// Runs every probe concurrently from `bind` and decides reachability by quorum: `Ok` if at
+/// least `quorum` of them succeed, otherwise an `Err` naming how many were reachable.
+pub async fn check_quorum(probes: &[Box<dyn Probe>], quorum: usize, bind: IpAddr) -> Result<()> {
+    let results = join_all(probes.iter().map(|p| p.check(bind))).await;
+    let successes = results.iter().filter(|r| r.is_ok()).count();
+
+    if successes >= quorum {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "only {}/{} targets reachable from {}, quorum is {}",
+            successes,
+            probes.len(),
+            bind,
+            quorum
+        ))
+    }
+}
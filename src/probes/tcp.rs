@@ -0,0 +1,50 @@
+use super::Probe;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpSocket;
+use tokio::time::timeout as with_timeout;
+
+/// Verifies a service is reachable by opening a TCP connection to `host:port`, bound to the
+/// given source address.
+pub struct TcpConnectProbe {
+    host: String,
+    port: u16,
+    timeout: Duration,
+}
+
+impl TcpConnectProbe {
+    pub fn new(host: String, port: u16, timeout: Duration) -> Self {
+        Self { host, port, timeout }
+    }
+}
+
+#[async_trait]
+impl Probe for TcpConnectProbe {
+    async fn check(&self, bind: IpAddr) -> Result<()> {
+        let target = tokio::net::lookup_host(format!("{}:{}", self.host, self.port))
+            .await?
+            .find(|addr| match (addr, bind) {
+                (SocketAddr::V4(_), IpAddr::V4(_)) => true,
+                (SocketAddr::V6(_), IpAddr::V6(_)) => true,
+                _ => bind.is_unspecified(),
+            })
+            .ok_or_else(|| anyhow!("no address found for {}:{}", self.host, self.port))?;
+
+        let socket = match target {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+
+        if !bind.is_unspecified() {
+            socket.bind(SocketAddr::new(bind, 0))?;
+        }
+
+        with_timeout(self.timeout, socket.connect(target))
+            .await
+            .map_err(|_| anyhow!("connecting to {} timed out after {:?}", target, self.timeout))??;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::RuntimeConfig;
+use crate::DetectionLoop;
+use anyhow::Result;
+use log::{error, info};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+/// Latest known status of one supervised watch target, kept here so the health endpoint can
+/// report on every target from a single place instead of one endpoint per target.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetState {
+    pub iface: String,
+    pub from: String,
+    pub nat_active: bool,
+    pub leader: bool,
+}
+
+/// Shared, per-target aggregated state. `DetectionLoop` updates its own entry every loop and
+/// removes it on shutdown.
+pub type StateMap = Arc<RwLock<HashMap<String, TargetState>>>;
+
+/// Spawns one detection task per configured watch target, and keeps spawning new ones as they
+/// appear in reloaded config (e.g. after a SIGHUP). Existing tasks reconfigure themselves in
+/// place by watching the same channel, so a NAT rule already applied is never dropped on
+/// reload. Returns once interrupted, after every task has torn down its own NAT rule.
+pub async fn run(mut rx: watch::Receiver<RuntimeConfig>, states: StateMap) {
+    let spawned: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+    let handles: Arc<Mutex<Vec<JoinHandle<()>>>> = Arc::new(Mutex::new(Vec::new()));
+
+    spawn_missing(&rx.borrow().clone(), &rx, &states, &spawned, &handles).await;
+
+    loop {
+        tokio::select! {
+            changed = rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let config = rx.borrow().clone();
+                spawn_missing(&config, &rx, &states, &spawned, &handles).await;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    let handles = std::mem::take(&mut *handles.lock().await);
+    for handle in handles {
+        let _ = handle.await;
+    }
+}
+
+async fn spawn_missing(
+    config: &RuntimeConfig,
+    rx: &watch::Receiver<RuntimeConfig>,
+    states: &StateMap,
+    spawned: &Arc<Mutex<HashSet<String>>>,
+    handles: &Arc<Mutex<Vec<JoinHandle<()>>>>,
+) {
+    for watch_config in &config.watches {
+        let mut guard = spawned.lock().await;
+        if !guard.insert(watch_config.iface.clone()) {
+            continue;
+        }
+        drop(guard);
+
+        let handle = spawn_target(watch_config.clone(), rx.clone(), states.clone(), spawned.clone());
+        handles.lock().await.push(handle);
+    }
+}
+
+fn spawn_target(
+    config: crate::config::WatchConfig,
+    rx: watch::Receiver<RuntimeConfig>,
+    states: StateMap,
+    spawned: Arc<Mutex<HashSet<String>>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let iface = config.iface.clone();
+        match DetectionLoop::new(config, rx, states).await {
+            Ok(mut detection_loop) => detection_loop.run().await,
+            Err(err) => error!("Failed to start watch target {}: {}", iface, err),
+        }
+        spawned.lock().await.remove(&iface);
+    })
+}
+
+/// Serves a `GET /health` endpoint reporting the aggregated state of every supervised target.
+pub async fn serve_health(addr: SocketAddr, states: StateMap) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Supervisor health endpoint listening on {}", addr);
+
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let states = states.clone();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            if socket.read(&mut buf).await.is_err() {
+                return;
+            }
+
+            let targets: Vec<TargetState> = states.read().await.values().cloned().collect();
+            let body = json!({ "targets": targets }).to_string();
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+
+            if let Err(err) = socket.write_all(response.as_bytes()).await {
+                error!("Error writing health response: {}", err);
+            }
+        });
+    }
+}